@@ -1,16 +1,101 @@
+use std::collections::VecDeque;
 use std::fmt;
 use std::io::{self, Read, Write};
 use std::os::unix::net::UnixStream;
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+mod config;
+mod response;
+
+use config::AppConfig;
+use response::{parse_raw_response, AgentConfig, RawResponse};
 
 // Constants
-const COMPONENT: &str = "com";
-const CONFIGURATION: &str = "active-response";
-const GETCONFIG_COMMAND: &str = "getconfig";
-const DEST_SOCKET: &str = "/var/ossec/queue/sockets/remote";
-const RECONNECT_DELAY: Duration = Duration::from_secs(1);
 const MAX_ATTEMPTS: u32 = 3;
+const BASE_RECONNECT_DELAY: Duration = Duration::from_millis(250);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+const MAX_MSG_SIZE: usize = 16 * 1024 * 1024;
+const IO_CHUNK_SIZE: usize = 16 * 1024;
+// Worker threads outnumber live-connection permits so the semaphore is the
+// actual bound on simultaneous sockets, not the thread count: idle workers
+// queue up on Semaphore::acquire() rather than connecting unbounded.
+const WORKER_FANOUT: usize = 4;
+
+// Slot for a single agent's outcome, shared across worker threads.
+type AgentOutcome = Option<Result<(AgentConfig, AgentMetrics), ShowError>>;
+
+// Per-agent instrumentation captured around a single successful round trip.
+#[derive(Debug, Default, Clone, Copy)]
+struct AgentMetrics {
+    bytes_sent: u64,
+    bytes_received: u64,
+    latency: Duration,
+}
+
+// Aggregate throughput summary printed once the whole fleet has been swept.
+#[derive(Debug, Default)]
+struct FleetMetrics {
+    agents_total: usize,
+    agents_succeeded: usize,
+    bytes_sent: u64,
+    bytes_received: u64,
+    elapsed: Duration,
+}
+
+impl fmt::Display for FleetMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let agents_per_sec = self.agents_total as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON);
+        write!(
+            f,
+            "agents: {}/{} succeeded | bytes sent: {} | bytes received: {} | elapsed: {:.2?} | throughput: {:.2} agents/sec",
+            self.agents_succeeded, self.agents_total, self.bytes_sent, self.bytes_received, self.elapsed, agents_per_sec
+        )
+    }
+}
+
+// Coordinates a shared target queries/sec across every worker thread so the
+// effective rate stays at `rate_per_sec` regardless of --concurrency, rather
+// than each worker independently sleeping and multiplying the limit by N.
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(rate_per_sec: f64) -> Self {
+        RateLimiter {
+            interval: Duration::from_secs_f64(1.0 / rate_per_sec),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn wait_for_slot(&self) {
+        let scheduled = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let scheduled = (*next_slot).max(Instant::now());
+            *next_slot = scheduled + self.interval;
+            scheduled
+        };
+        let now = Instant::now();
+        if scheduled > now {
+            thread::sleep(scheduled - now);
+        }
+    }
+}
+
+// Exponential backoff with jitter: delay = min(base * 2^attempt, cap) * (1.0 + rand(0.0..0.5)).
+// Staggers reconnect storms when the `remote` daemon restarts and every agent retries at once.
+fn backoff_with_jitter(attempt: u32) -> Duration {
+    let exp = BASE_RECONNECT_DELAY.saturating_mul(2u32.saturating_pow(attempt));
+    let capped = exp.min(MAX_RECONNECT_DELAY);
+    let jitter = rand::thread_rng().gen_range(0.0..0.5);
+    capped.mul_f64(1.0 + jitter)
+}
 
 // Custom error type
 #[derive(Debug)]
@@ -34,6 +119,36 @@ impl From<std::string::FromUtf8Error> for ShowError {
     }
 }
 
+// Counting semaphore bounding simultaneous connections to the target socket
+// (AppConfig::socket_path)
+struct Semaphore {
+    permits: Mutex<usize>,
+    cvar: Condvar,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Semaphore {
+            permits: Mutex::new(permits),
+            cvar: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.cvar.wait(permits).unwrap();
+        }
+        *permits -= 1;
+    }
+
+    fn release(&self) {
+        let mut permits = self.permits.lock().unwrap();
+        *permits += 1;
+        self.cvar.notify_one();
+    }
+}
+
 // Socket wrapper
 struct SocketInstance {
     stream: Option<UnixStream>,
@@ -55,23 +170,85 @@ impl SocketInstance {
         Ok(())
     }
 
-    fn send(&mut self, msg: &[u8]) -> Result<(), ShowError> {
-        let stream = self.stream.as_mut().ok_or(ShowError("Socket not connected".into()))?;
+    fn send_raw(&mut self, msg: &[u8]) -> io::Result<()> {
+        let stream = self.stream.as_mut().ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "socket not connected"))?;
         let header = (msg.len() as u32).to_le_bytes();
         stream.write_all(&header)?;
-        stream.write_all(msg)?;
+        for chunk in msg.chunks(IO_CHUNK_SIZE) {
+            stream.write_all(chunk)?;
+        }
         Ok(())
     }
 
-    fn receive(&mut self) -> Result<Vec<u8>, ShowError> {
-        let stream = self.stream.as_mut().ok_or(ShowError("Socket not connected".into()))?;
+    // Reads the 4-byte LE length header, rejects it outright if it exceeds
+    // MAX_MSG_SIZE (before allocating anything), then accumulates the payload
+    // in IO_CHUNK_SIZE reads so a declared length never drives a single huge
+    // allocation. Returns exactly `size` bytes or an explicit error — never a
+    // silent truncation.
+    fn receive_raw(&mut self) -> io::Result<Vec<u8>> {
+        let stream = self.stream.as_mut().ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "socket not connected"))?;
         let mut header = [0; 4];
         stream.read_exact(&mut header)?;
         let size = u32::from_le_bytes(header) as usize;
-        let mut buffer = vec![0; size];
-        stream.read_exact(&mut buffer)?;
+        if size > MAX_MSG_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("declared message size {} exceeds MAX_MSG_SIZE {}", size, MAX_MSG_SIZE),
+            ));
+        }
+
+        let mut buffer = Vec::new();
+        let mut remaining = size;
+        let mut chunk = [0u8; IO_CHUNK_SIZE];
+        while remaining > 0 {
+            let to_read = remaining.min(IO_CHUNK_SIZE);
+            let n = stream.read(&mut chunk[..to_read])?;
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "connection closed before full message was received",
+                ));
+            }
+            buffer.reserve(n);
+            buffer.extend_from_slice(&chunk[..n]);
+            remaining -= n;
+        }
         Ok(buffer)
     }
+
+    // Whether a dropped connection is worth transparently reconnecting and
+    // replaying the request for, as opposed to a hard failure.
+    fn is_reconnectable(error: &io::Error) -> bool {
+        matches!(
+            error.kind(),
+            io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::UnexpectedEof
+        )
+    }
+
+    // Send `msg` and wait for the response, transparently reconnecting and
+    // replaying the request (with exponential backoff + jitter between
+    // attempts) if the link drops mid-exchange.
+    fn request(&mut self, msg: &[u8]) -> Result<Vec<u8>, ShowError> {
+        let mut attempt = 0;
+
+        loop {
+            let outcome = self.send_raw(msg).and_then(|_| self.receive_raw());
+            match outcome {
+                Ok(buffer) => return Ok(buffer),
+                Err(e) if attempt < MAX_RECONNECT_ATTEMPTS && Self::is_reconnectable(&e) => {
+                    attempt += 1;
+                    let delay = backoff_with_jitter(attempt);
+                    eprintln!(
+                        "Connection to {} dropped ({}); reconnecting in {:?} (attempt {}/{})",
+                        self.path, e, delay, attempt, MAX_RECONNECT_ATTEMPTS
+                    );
+                    thread::sleep(delay);
+                    self.connect()?;
+                }
+                Err(e) => return Err(ShowError::from(e)),
+            }
+        }
+    }
 }
 
 impl Drop for SocketInstance {
@@ -82,60 +259,160 @@ impl Drop for SocketInstance {
     }
 }
 
-// Process a single agent
-fn process_agent(agent_id: &str) -> Result<String, ShowError> {
-    let mut socket = SocketInstance::new(DEST_SOCKET)?;
-    println!("Connected to socket for agent {}: {}", agent_id, DEST_SOCKET);
+// Process a single agent. `semaphore` caps how many of these run concurrently
+// against the target socket regardless of how many worker threads are in flight.
+fn process_agent(agent_id: &str, config: &AppConfig, semaphore: &Semaphore) -> Result<(AgentConfig, AgentMetrics), ShowError> {
+    semaphore.acquire();
+    let result = process_agent_inner(agent_id, config);
+    semaphore.release();
+    result
+}
+
+fn process_agent_inner(agent_id: &str, config: &AppConfig) -> Result<(AgentConfig, AgentMetrics), ShowError> {
+    let mut socket = SocketInstance::new(&config.socket_path)?;
+    eprintln!("Connected to socket for agent {}: {}", agent_id, config.socket_path);
 
-    let msg = format!("{} {} {} {}", agent_id, COMPONENT, GETCONFIG_COMMAND, CONFIGURATION);
-    println!("Agent {}: Encoded MSG: {:?}", agent_id, msg.as_bytes());
+    let msg = config.request.encode(agent_id);
+    eprintln!("Agent {}: Encoded MSG: {:?}", agent_id, msg.as_bytes());
 
-    socket.send(msg.as_bytes())?;
-    println!("Message sent to agent {}", agent_id);
+    let start = Instant::now();
+    let rec_msg_bytes = socket.request(msg.as_bytes())?;
+    let metrics = AgentMetrics {
+        bytes_sent: msg.len() as u64,
+        bytes_received: rec_msg_bytes.len() as u64,
+        latency: start.elapsed(),
+    };
 
-    let rec_msg_bytes = socket.receive()?;
     let rec_msg = String::from_utf8(rec_msg_bytes)?;
-    let mut parts = rec_msg.splitn(2, ' ');
-    let rec_msg_ok = parts.next().unwrap_or("");
-    let rec_msg_body = parts.next().unwrap_or("");
 
-    if rec_msg_ok == "err" && rec_msg_body.contains("Cannot send request") {
-        return Err(ShowError("Agent is not connected".into()));
-    }
+    let agent_config = match parse_raw_response(&rec_msg)? {
+        RawResponse::Err(body) if body.contains("Cannot send request") => {
+            return Err(ShowError("Agent is not connected".into()));
+        }
+        RawResponse::Err(body) => return Err(ShowError(format!("agent {} returned an error: {}", agent_id, body))),
+        RawResponse::Ok(value) => AgentConfig {
+            agent_id: agent_id.to_string(),
+            section: config.request.section.clone(),
+            config: response::parse_section(&config.request.section, value)?,
+        },
+    };
+
+    Ok((agent_config, metrics))
+}
 
-    Ok(format!("rec_msg_ok: {} | rec_msg_body: {}", rec_msg_ok, rec_msg_body))
+// Retry wrapper around process_agent, run by a worker thread.
+fn process_agent_with_retries(agent_id: &str, config: &AppConfig, semaphore: &Semaphore) -> Result<(AgentConfig, AgentMetrics), ShowError> {
+    let mut attempts = 0;
+
+    loop {
+        match process_agent(agent_id, config, semaphore) {
+            Ok(response) => return Ok(response),
+            Err(e) => {
+                attempts += 1;
+                if attempts >= MAX_ATTEMPTS {
+                    return Err(e);
+                }
+                let delay = backoff_with_jitter(attempts);
+                eprintln!("Attempt {} failed for agent {}: {}. Retrying in {:?}...", attempts, agent_id, e, delay);
+                thread::sleep(delay);
+            }
+        }
+    }
 }
 
 fn main() -> io::Result<()> {
+    let config = Arc::new(AppConfig::load());
+
     println!("Enter agent IDs (one per line). Press Ctrl+D (Unix) or Ctrl+Z (Windows) when finished:");
-    
+
     let agent_ids: Vec<String> = io::stdin().lines()
-        .filter_map(|line| line.ok())
+        .map_while(Result::ok)
         .filter(|line| !line.trim().is_empty())
         .collect();
 
-    for agent_id in agent_ids {
-        let mut attempts = 0;
+    let queue: Arc<Mutex<VecDeque<(usize, String)>>> = Arc::new(Mutex::new(
+        agent_ids.iter().cloned().enumerate().collect(),
+    ));
+    let semaphore = Arc::new(Semaphore::new(config.concurrency));
+    let rate_limiter = config.rate_limit.map(|rate| Arc::new(RateLimiter::new(rate)));
+    let results: Arc<Mutex<Vec<AgentOutcome>>> =
+        Arc::new(Mutex::new((0..agent_ids.len()).map(|_| None).collect()));
 
-        while attempts < MAX_ATTEMPTS {
-            match process_agent(&agent_id) {
-                Ok(response) => {
-                    println!("Message received from agent {}", agent_id);
-                    println!("{}", response);
-                    break;
-                }
-                Err(e) => {
-                    attempts += 1;
-                    if attempts >= MAX_ATTEMPTS {
+    let start = Instant::now();
+    let worker_count = (config.concurrency * WORKER_FANOUT).min(agent_ids.len()).max(1);
+    let mut workers = Vec::with_capacity(worker_count);
+    for _ in 0..worker_count {
+        let queue = Arc::clone(&queue);
+        let config = Arc::clone(&config);
+        let semaphore = Arc::clone(&semaphore);
+        let rate_limiter = rate_limiter.clone();
+        let results = Arc::clone(&results);
+
+        workers.push(thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop_front();
+            let (index, agent_id) = match next {
+                Some(item) => item,
+                None => break,
+            };
+
+            if let Some(rate_limiter) = &rate_limiter {
+                rate_limiter.wait_for_slot();
+            }
+
+            let outcome = process_agent_with_retries(&agent_id, &config, &semaphore);
+            results.lock().unwrap()[index] = Some(outcome);
+        }));
+    }
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    let ordered_results = Arc::try_unwrap(results).unwrap().into_inner().unwrap();
+
+    let mut fleet_metrics = FleetMetrics {
+        agents_total: agent_ids.len(),
+        elapsed: start.elapsed(),
+        ..Default::default()
+    };
+
+    if config.json {
+        let configs: Vec<AgentConfig> = agent_ids.iter().zip(ordered_results)
+            .filter_map(|(agent_id, outcome)| {
+                match outcome.expect("every queued agent is processed exactly once") {
+                    Ok((config, metrics)) => {
+                        fleet_metrics.agents_succeeded += 1;
+                        fleet_metrics.bytes_sent += metrics.bytes_sent;
+                        fleet_metrics.bytes_received += metrics.bytes_received;
+                        Some(config)
+                    }
+                    Err(e) => {
                         eprintln!("Error processing agent {}: {}", agent_id, e);
-                    } else {
-                        eprintln!("Attempt {} failed for agent {}: {}. Retrying...", attempts, agent_id, e);
-                        thread::sleep(RECONNECT_DELAY);
+                        None
                     }
                 }
+            })
+            .collect();
+        match serde_json::to_string_pretty(&configs) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error serializing agent configs: {}", e),
+        }
+    } else {
+        for (agent_id, outcome) in agent_ids.iter().zip(ordered_results) {
+            match outcome.expect("every queued agent is processed exactly once") {
+                Ok((config, metrics)) => {
+                    fleet_metrics.agents_succeeded += 1;
+                    fleet_metrics.bytes_sent += metrics.bytes_sent;
+                    fleet_metrics.bytes_received += metrics.bytes_received;
+                    println!("Message received from agent {}", agent_id);
+                    println!("{:?} ({:.2?} round trip)", config, metrics.latency);
+                }
+                Err(e) => eprintln!("Error processing agent {}: {}", agent_id, e),
             }
         }
     }
 
+    eprintln!("{}", fleet_metrics);
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,88 @@
+// Response-parsing subsystem: turns the raw `ok <json>` / `err <message>`
+// wire format from the `remote` daemon into typed values.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ShowError;
+
+impl From<serde_json::Error> for ShowError {
+    fn from(error: serde_json::Error) -> Self {
+        ShowError(error.to_string())
+    }
+}
+
+// The leading token of every `remote` response, before the body of the
+// queried section is interpreted.
+#[derive(Debug)]
+pub enum RawResponse {
+    Ok(serde_json::Value),
+    Err(String),
+}
+
+// Split `ok <json>` / `err <message>` on the first space and parse the `ok`
+// body as JSON. An unrecognized leading token is a protocol error, not a
+// business-level one, so it surfaces as `ShowError` rather than `Err(..)`.
+pub fn parse_raw_response(rec_msg: &str) -> Result<RawResponse, ShowError> {
+    let mut parts = rec_msg.splitn(2, ' ');
+    let tag = parts.next().unwrap_or("");
+    let body = parts.next().unwrap_or("");
+
+    match tag {
+        "ok" => Ok(RawResponse::Ok(serde_json::from_str(body)?)),
+        "err" => Ok(RawResponse::Err(body.to_string())),
+        other => Err(ShowError(format!("unrecognized response tag: {}", other))),
+    }
+}
+
+// One entry of the `active-response` configuration block. The exact wire
+// shape isn't authoritative, so every field is tolerant of being absent
+// rather than failing the whole agent's parse over one missing key.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActiveResponseEntry {
+    #[serde(default)]
+    pub command: String,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    #[serde(default)]
+    pub rules_id: Vec<String>,
+    #[serde(default)]
+    pub timeout: u64,
+}
+
+// The `active-response` block of a `getconfig` response body.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActiveResponseConfig {
+    #[serde(rename = "active-response", default)]
+    pub entries: Vec<ActiveResponseEntry>,
+}
+
+// The parsed body of a `getconfig` response, keyed off whichever section was
+// actually queried. `active-response` gets the typed model above; every other
+// component/section (`logcollector localfile`, `syscheck syscheck`, ...) is
+// returned as the raw JSON value rather than silently discarded.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SectionConfig {
+    ActiveResponse(ActiveResponseConfig),
+    Raw(serde_json::Value),
+}
+
+// A single agent's parsed, machine-readable configuration.
+#[derive(Debug, Serialize)]
+pub struct AgentConfig {
+    pub agent_id: String,
+    pub section: String,
+    pub config: SectionConfig,
+}
+
+// Parse `value` according to `section`: the typed `ActiveResponseConfig` for
+// `active-response`, the raw JSON value for anything else.
+pub fn parse_section(section: &str, value: serde_json::Value) -> Result<SectionConfig, ShowError> {
+    if section == "active-response" {
+        Ok(SectionConfig::ActiveResponse(serde_json::from_value(value)?))
+    } else {
+        Ok(SectionConfig::Raw(value))
+    }
+}